@@ -0,0 +1,615 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+
+use crate::wireguard::WireGuardConfig;
+
+/// Returns the `proxies` list if `text` is a Clash / Clash.Meta YAML document.
+pub fn find_proxies(text: &str) -> Result<Option<Vec<Value>>, String> {
+    // Base64 and plain link lists can never contain this key, so skip the YAML parser for them
+    if !text.contains("proxies:") {
+        return Ok(None);
+    }
+    let doc: Value =
+        serde_yaml::from_str(text).map_err(|e| format!("Invalid Clash config: {}", e))?;
+    match doc.get("proxies") {
+        Some(Value::Sequence(proxies)) => Ok(Some(proxies.clone())),
+        _ => Ok(None),
+    }
+}
+
+// Clash allows most scalars to be written as either strings or numbers
+fn field(proxy: &Mapping, key: &str) -> Option<String> {
+    match proxy.get(key)? {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn required(proxy: &Mapping, key: &str) -> Result<String, String> {
+    field(proxy, key)
+        .filter(|v| !v.is_empty())
+        .ok_or(format!("Missing {}", key))
+}
+
+fn flag(proxy: &Mapping, key: &str) -> bool {
+    matches!(field(proxy, key).as_deref(), Some("true") | Some("1"))
+}
+
+fn list(proxy: &Mapping, key: &str) -> Vec<String> {
+    match proxy.get(key) {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) => s.split(',').map(|v| v.trim().to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+fn sub_map<'a>(proxy: &'a Mapping, key: &str) -> Option<&'a Mapping> {
+    proxy.get(key).and_then(|v| v.as_mapping())
+}
+
+fn server_authority(proxy: &Mapping) -> Result<String, String> {
+    let server = required(proxy, "server")?;
+    let port = required(proxy, "port")?
+        .parse::<u16>()
+        .map_err(|_| "Invalid port".to_string())?;
+    if server.contains(':') {
+        Ok(format!("[{}]:{}", server, port))
+    } else {
+        Ok(format!("{}:{}", server, port))
+    }
+}
+
+fn query(params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
+        .collect();
+    format!("?{}", pairs.join("&"))
+}
+
+// Network/transport options shared by vmess, vless and trojan entries
+struct Transport {
+    net: String,
+    host: String,
+    path: String,
+}
+
+fn transport(proxy: &Mapping) -> Transport {
+    let net = field(proxy, "network").unwrap_or_else(|| "tcp".to_string());
+    let mut host = String::new();
+    let mut path = String::new();
+
+    match net.as_str() {
+        "ws" => {
+            if let Some(opts) = sub_map(proxy, "ws-opts") {
+                path = field(opts, "path").unwrap_or_default();
+                if let Some(headers) = sub_map(opts, "headers") {
+                    host = field(headers, "Host")
+                        .or(field(headers, "host"))
+                        .unwrap_or_default();
+                }
+                if let Some(ed) = field(opts, "max-early-data") {
+                    let sep = if path.contains('?') { '&' } else { '?' };
+                    path = format!("{}{}ed={}", path, sep, ed);
+                }
+            }
+        }
+        "grpc" => {
+            if let Some(opts) = sub_map(proxy, "grpc-opts") {
+                path = field(opts, "grpc-service-name").unwrap_or_default();
+            }
+        }
+        "h2" => {
+            if let Some(opts) = sub_map(proxy, "h2-opts") {
+                host = list(opts, "host").join(",");
+                path = field(opts, "path").unwrap_or_default();
+            }
+        }
+        "http" => {
+            if let Some(opts) = sub_map(proxy, "http-opts") {
+                path = list(opts, "path").into_iter().next().unwrap_or_default();
+                if let Some(headers) = sub_map(opts, "headers") {
+                    host = list(headers, "Host").join(",");
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Transport { net, host, path }
+}
+
+fn push_transport_params(params: &mut Vec<(String, String)>, transport: &Transport) {
+    // Clash's "http" network is HTTP/1.1 header obfuscation on top of TCP
+    if transport.net == "http" {
+        params.push(("type".into(), "tcp".into()));
+        params.push(("headerType".into(), "http".into()));
+    } else {
+        params.push(("type".into(), transport.net.clone()));
+    }
+    if !transport.host.is_empty() {
+        params.push(("host".into(), transport.host.clone()));
+    }
+    if !transport.path.is_empty() {
+        let key = if transport.net == "grpc" {
+            "serviceName"
+        } else {
+            "path"
+        };
+        params.push((key.into(), transport.path.clone()));
+    }
+}
+
+fn shadowsocks_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let cipher = required(proxy, "cipher")?;
+    let password = required(proxy, "password")?;
+    let user_info = general_purpose::URL_SAFE_NO_PAD.encode(format!("{}:{}", cipher, password));
+
+    let mut params = vec![];
+    if let Some(plugin) = field(proxy, "plugin") {
+        let opts = sub_map(proxy, "plugin-opts");
+        let opt = |key: &str| opts.and_then(|o| field(o, key)).unwrap_or_default();
+        let plugin = match plugin.as_str() {
+            "obfs" => {
+                let mut plugin = format!("obfs-local;obfs={}", opt("mode"));
+                if !opt("host").is_empty() {
+                    plugin.push_str(&format!(";obfs-host={}", opt("host")));
+                }
+                plugin
+            }
+            "v2ray-plugin" => {
+                let mut plugin = "v2ray-plugin".to_string();
+                if opt("mode") == "quic" {
+                    plugin.push_str(";mode=quic");
+                }
+                if opt("tls") == "true" {
+                    plugin.push_str(";tls");
+                }
+                if !opt("host").is_empty() {
+                    plugin.push_str(&format!(";host={}", opt("host")));
+                }
+                if !opt("path").is_empty() {
+                    plugin.push_str(&format!(";path={}", opt("path")));
+                }
+                plugin
+            }
+            other => return Err(format!("Shadowsocks plugin {} not supported", other)),
+        };
+        params.push(("plugin".to_string(), plugin));
+    }
+
+    Ok(format!(
+        "ss://{}@{}{}#{}",
+        user_info,
+        server_authority(proxy)?,
+        query(&params),
+        urlencoding::encode(name)
+    ))
+}
+
+fn vmess_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let transport = transport(proxy);
+    let (net, header_type) = if transport.net == "http" {
+        ("tcp".to_string(), "http")
+    } else {
+        (transport.net.clone(), "none")
+    };
+
+    let config = json!({
+        "v": "2",
+        "ps": name,
+        "add": required(proxy, "server")?,
+        "port": required(proxy, "port")?,
+        "id": required(proxy, "uuid")?,
+        "aid": field(proxy, "alterId").unwrap_or_else(|| "0".to_string()),
+        "scy": field(proxy, "cipher").unwrap_or_else(|| "auto".to_string()),
+        "net": net,
+        "type": header_type,
+        "host": transport.host,
+        "path": transport.path,
+        "tls": if flag(proxy, "tls") { "tls" } else { "" },
+        "sni": field(proxy, "servername").unwrap_or_default(),
+        "alpn": list(proxy, "alpn").join(","),
+        "fp": field(proxy, "client-fingerprint").unwrap_or_default(),
+        "allowInsecure": if flag(proxy, "skip-cert-verify") { "1" } else { "0" }
+    });
+
+    Ok(format!(
+        "vmess://{}",
+        general_purpose::STANDARD.encode(config.to_string())
+    ))
+}
+
+fn vless_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let uuid = required(proxy, "uuid")?;
+    let mut params = vec![];
+
+    if let Some(flow) = field(proxy, "flow").filter(|f| !f.is_empty()) {
+        params.push(("flow".to_string(), flow));
+    }
+    if let Some(reality) = sub_map(proxy, "reality-opts") {
+        params.push(("security".into(), "reality".into()));
+        params.push(("pbk".into(), required(reality, "public-key")?));
+        if let Some(sid) = field(reality, "short-id") {
+            params.push(("sid".into(), sid));
+        }
+    } else if flag(proxy, "tls") {
+        params.push(("security".into(), "tls".into()));
+    }
+    if let Some(sni) = field(proxy, "servername") {
+        params.push(("sni".into(), sni));
+    }
+    if let Some(fp) = field(proxy, "client-fingerprint") {
+        params.push(("fp".into(), fp));
+    }
+    push_transport_params(&mut params, &transport(proxy));
+
+    Ok(format!(
+        "vless://{}@{}{}#{}",
+        urlencoding::encode(&uuid),
+        server_authority(proxy)?,
+        query(&params),
+        urlencoding::encode(name)
+    ))
+}
+
+fn trojan_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let password = required(proxy, "password")?;
+    let mut params = vec![];
+
+    if let Some(sni) = field(proxy, "sni") {
+        params.push(("sni".into(), sni));
+    }
+    if let Some(fp) = field(proxy, "client-fingerprint") {
+        params.push(("fp".into(), fp));
+    }
+    let alpn = list(proxy, "alpn");
+    if !alpn.is_empty() {
+        params.push(("alpn".into(), alpn.join(",")));
+    }
+    if flag(proxy, "skip-cert-verify") {
+        params.push(("allowInsecure".into(), "1".into()));
+    }
+    push_transport_params(&mut params, &transport(proxy));
+
+    Ok(format!(
+        "trojan://{}@{}{}#{}",
+        urlencoding::encode(&password),
+        server_authority(proxy)?,
+        query(&params),
+        urlencoding::encode(name)
+    ))
+}
+
+fn hysteria2_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let password = field(proxy, "password")
+        .or(field(proxy, "auth"))
+        .ok_or("Missing password")?;
+    let mut params = vec![];
+
+    if let Some(sni) = field(proxy, "sni") {
+        params.push(("sni".into(), sni));
+    }
+    if flag(proxy, "skip-cert-verify") {
+        params.push(("insecure".into(), "1".into()));
+    }
+    if let Some(obfs) = field(proxy, "obfs").filter(|o| !o.is_empty()) {
+        params.push(("obfs".into(), obfs));
+        params.push((
+            "obfs-password".into(),
+            field(proxy, "obfs-password").unwrap_or_default(),
+        ));
+    }
+
+    Ok(format!(
+        "hysteria2://{}@{}{}#{}",
+        urlencoding::encode(&password),
+        server_authority(proxy)?,
+        query(&params),
+        urlencoding::encode(name)
+    ))
+}
+
+fn tuic_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let uuid = required(proxy, "uuid")?;
+    let password = required(proxy, "password")?;
+    let mut params = vec![];
+
+    if let Some(cc) = field(proxy, "congestion-controller") {
+        params.push(("congestion_control".into(), cc));
+    }
+    if let Some(mode) = field(proxy, "udp-relay-mode") {
+        params.push(("udp_relay_mode".into(), mode));
+    }
+    let alpn = list(proxy, "alpn");
+    if !alpn.is_empty() {
+        params.push(("alpn".into(), alpn.join(",")));
+    }
+    if let Some(sni) = field(proxy, "sni") {
+        params.push(("sni".into(), sni));
+    }
+    if flag(proxy, "skip-cert-verify") {
+        params.push(("allow_insecure".into(), "1".into()));
+    }
+    if flag(proxy, "disable-sni") {
+        params.push(("disable_sni".into(), "1".into()));
+    }
+
+    Ok(format!(
+        "tuic://{}:{}@{}{}#{}",
+        urlencoding::encode(&uuid),
+        urlencoding::encode(&password),
+        server_authority(proxy)?,
+        query(&params),
+        urlencoding::encode(name)
+    ))
+}
+
+fn wireguard_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let mut addresses = vec![];
+    for key in ["ip", "ipv6"] {
+        if let Some(ip) = field(proxy, key).filter(|ip| !ip.is_empty()) {
+            // Clash omits the prefix length for single addresses
+            if ip.contains('/') {
+                addresses.push(ip);
+            } else if ip.contains(':') {
+                addresses.push(format!("{}/128", ip));
+            } else {
+                addresses.push(format!("{}/32", ip));
+            }
+        }
+    }
+    if addresses.is_empty() {
+        return Err("Missing ip".to_string());
+    }
+
+    let config = WireGuardConfig {
+        private_key: required(proxy, "private-key")?,
+        addresses,
+        dns: list(proxy, "dns"),
+        mtu: field(proxy, "mtu").and_then(|m| m.parse().ok()),
+        peer_public_key: required(proxy, "public-key")?,
+        preshared_key: field(proxy, "pre-shared-key").filter(|k| !k.is_empty()),
+        allowed_ips: list(proxy, "allowed-ips"),
+        endpoint_host: required(proxy, "server")?,
+        endpoint_port: required(proxy, "port")?
+            .parse()
+            .map_err(|_| "Invalid port".to_string())?,
+    };
+    Ok(config.to_link(name))
+}
+
+fn socks_link(proxy: &Mapping, name: &str) -> Result<String, String> {
+    let user_info = match (field(proxy, "username"), field(proxy, "password")) {
+        (Some(user), Some(pass)) => format!(
+            "{}:{}@",
+            urlencoding::encode(&user),
+            urlencoding::encode(&pass)
+        ),
+        (Some(user), None) => format!("{}@", urlencoding::encode(&user)),
+        _ => String::new(),
+    };
+    Ok(format!(
+        "socks5://{}{}#{}",
+        user_info,
+        server_authority(proxy)?,
+        urlencoding::encode(name)
+    ))
+}
+
+/// Converts a single Clash proxy entry into a share link that `parse_outbound` understands.
+pub fn proxy_to_link(proxy: &Value) -> Result<String, String> {
+    let proxy = proxy.as_mapping().ok_or("Proxy entry is not a mapping")?;
+    let name = field(proxy, "name").unwrap_or_else(|| "Imported Profile".to_string());
+    let kind = required(proxy, "type")?;
+
+    match kind.as_str() {
+        "ss" => shadowsocks_link(proxy, &name),
+        "vmess" => vmess_link(proxy, &name),
+        "vless" => vless_link(proxy, &name),
+        "trojan" => trojan_link(proxy, &name),
+        "hysteria2" | "hy2" => hysteria2_link(proxy, &name),
+        "tuic" => tuic_link(proxy, &name),
+        "wireguard" => wireguard_link(proxy, &name),
+        "socks5" => socks_link(proxy, &name),
+        other => Err(format!("Proxy type {} not supported", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::share_link::ShareLink;
+
+    const CONFIG: &str = r#"
+port: 7890
+proxies:
+  - name: ss-obfs
+    type: ss
+    server: ss.example
+    port: 8388
+    cipher: chacha20-ietf-poly1305
+    password: secret
+    plugin: obfs
+    plugin-opts:
+      mode: http
+      host: bing.com
+  - name: vmess-ws
+    type: vmess
+    server: vmess.example
+    port: 443
+    uuid: 5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718
+    alterId: 0
+    cipher: auto
+    tls: true
+    servername: cdn.example
+    network: ws
+    ws-opts:
+      path: /ray
+      headers:
+        Host: cdn.example
+  - name: vmess-grpc
+    type: vmess
+    server: vmess.example
+    port: 443
+    uuid: 5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718
+    alterId: 0
+    cipher: auto
+    tls: true
+    network: grpc
+    grpc-opts:
+      grpc-service-name: gun
+  - name: vless-reality
+    type: vless
+    server: 203.0.113.7
+    port: 443
+    uuid: 5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718
+    flow: xtls-rprx-vision
+    tls: true
+    servername: www.microsoft.com
+    client-fingerprint: chrome
+    reality-opts:
+      public-key: SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc
+      short-id: 6ba85179e30d4fc2
+  - name: trojan
+    type: trojan
+    server: trojan.example
+    port: 443
+    password: hunter2
+    sni: trojan.example
+    skip-cert-verify: true
+  - name: hy2
+    type: hysteria2
+    server: hy2.example
+    port: 8443
+    password: hy-pass
+    obfs: salamander
+    obfs-password: obfs-pass
+  - name: tuic
+    type: tuic
+    server: tuic.example
+    port: 443
+    uuid: 5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718
+    password: tuic-pass
+    congestion-controller: bbr
+    udp-relay-mode: native
+    alpn: [h3]
+  - name: wg
+    type: wireguard
+    server: wg.example
+    port: 51820
+    ip: 10.0.0.2
+    ipv6: fd00::2
+    private-key: yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+    public-key: xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+    mtu: 1380
+  - name: socks
+    type: socks5
+    server: socks.example
+    port: 1080
+    username: user
+    password: pass
+  - name: snell
+    type: snell
+    server: snell.example
+    port: 443
+    psk: abc
+"#;
+
+    fn outbounds() -> Vec<Result<serde_json::Value, String>> {
+        find_proxies(CONFIG)
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|proxy| {
+                let link = ShareLink::parse_checked(&proxy_to_link(proxy)?)
+                    .map_err(|e| format!("{:?}", e))?;
+                link.to_outbound(&Default::default())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn converts_every_supported_proxy_type() {
+        let outbounds = outbounds();
+        let get = |i: usize| outbounds[i].as_ref().unwrap();
+
+        let ss = get(0);
+        assert_eq!(ss["type"], "shadowsocks");
+        assert_eq!(ss["method"], "chacha20-ietf-poly1305");
+        assert_eq!(ss["plugin"], "obfs-local");
+        assert_eq!(ss["plugin_opts"], "obfs=http;obfs-host=bing.com");
+
+        let ws = get(1);
+        assert_eq!(ws["type"], "vmess");
+        assert_eq!(ws["transport"]["type"], "ws");
+        assert_eq!(ws["transport"]["path"], "/ray");
+        assert_eq!(ws["transport"]["headers"]["Host"], "cdn.example");
+        assert_eq!(ws["tls"]["server_name"], "cdn.example");
+
+        let grpc = get(2);
+        assert_eq!(grpc["transport"]["type"], "grpc");
+        assert_eq!(grpc["transport"]["service_name"], "gun");
+
+        let reality = get(3);
+        assert_eq!(reality["type"], "vless");
+        assert_eq!(reality["flow"], "xtls-rprx-vision");
+        assert_eq!(reality["tls"]["server_name"], "www.microsoft.com");
+        assert_eq!(
+            reality["tls"]["reality"]["public_key"],
+            "SbVKOEMjK0sIlbwg4akyBg5mL5KZwwB-ed4eEE7YnRc"
+        );
+        assert_eq!(reality["tls"]["reality"]["short_id"], "6ba85179e30d4fc2");
+        assert_eq!(reality["tls"]["utls"]["fingerprint"], "chrome");
+
+        let trojan = get(4);
+        assert_eq!(trojan["type"], "trojan");
+        assert_eq!(trojan["password"], "hunter2");
+        assert_eq!(trojan["tls"]["insecure"], true);
+
+        let hy2 = get(5);
+        assert_eq!(hy2["type"], "hysteria2");
+        assert_eq!(hy2["password"], "hy-pass");
+        assert_eq!(hy2["obfs"]["type"], "salamander");
+        assert_eq!(hy2["obfs"]["password"], "obfs-pass");
+
+        let tuic = get(6);
+        assert_eq!(tuic["type"], "tuic");
+        assert_eq!(tuic["congestion_control"], "bbr");
+        assert_eq!(tuic["udp_relay_mode"], "native");
+        assert_eq!(tuic["tls"]["alpn"], json!(["h3"]));
+
+        let wg = get(7);
+        assert_eq!(wg["type"], "wireguard");
+        assert_eq!(wg["local_address"], json!(["10.0.0.2/32", "fd00::2/128"]));
+        assert_eq!(wg["mtu"], 1380);
+
+        let socks = get(8);
+        assert_eq!(socks["type"], "socks");
+        assert_eq!(socks["username"], "user");
+        assert_eq!(socks["password"], "pass");
+    }
+
+    #[test]
+    fn reports_unsupported_types() {
+        let outbounds = outbounds();
+        assert_eq!(
+            outbounds[9].as_ref().unwrap_err(),
+            "Proxy type snell not supported"
+        );
+        assert!(find_proxies("vmess://abc\ntrojan://def").unwrap().is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::share_link::ShareLink;
+use crate::{singbox, Profile};
+
+/// What to do when an imported profile points at a server that is already in the list.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    #[default]
+    Skip,
+    Replace,
+    KeepBoth,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct ImportCounts {
+    pub added: usize,
+    pub updated: usize,
+    /// Duplicates left out because of the policy. Entries that failed to parse are
+    /// listed separately as `skipped`.
+    pub duplicates: usize,
+    /// Servers left out by the subscription's filter rules.
+    pub filtered: usize,
+}
+
+fn outbound_identity(outbound: &Value) -> Option<String> {
+    let field = |key: &str| outbound.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let server = outbound.get("server")?.as_str()?;
+    let port = outbound.get("server_port")?.as_u64()?;
+    let protocol = singbox::protocol_label(outbound);
+
+    // Mirrors ShareLink::identity so a link and a sing-box outbound for the same account match
+    let credential = match protocol.as_str() {
+        "vless" | "vmess" => field("uuid").to_lowercase(),
+        "tuic" => format!("{}:{}", field("uuid").to_lowercase(), field("password")),
+        "ss" => format!("{}:{}", field("method"), field("password")),
+        "wireguard" => field("private_key").to_string(),
+        "socks" => format!("{}:{}", field("username"), field("password")),
+        _ => field("password").to_string(),
+    };
+    Some(format!(
+        "{}|{}|{}|{}",
+        protocol,
+        server.to_lowercase(),
+        port,
+        credential
+    ))
+}
+
+/// The normalized identity used to spot duplicates, or None if the profile cannot be parsed.
+pub fn profile_identity(profile: &Profile) -> Option<String> {
+    match &profile.outbound {
+        Some(outbound) => outbound_identity(outbound),
+        None => ShareLink::parse(&profile.config_link)
+            .ok()
+            .map(|link| link.identity()),
+    }
+}
+
+// Replace keeps the existing id and usage so the profile stays selected and its counters survive
+pub fn replace_profile(existing: &mut Profile, imported: Profile) {
+    existing.name = imported.name;
+    existing.protocol = imported.protocol;
+    existing.config_link = imported.config_link;
+    existing.outbound = imported.outbound;
+}
+
+/// Appends `imported` to `profiles`, resolving duplicates with `policy`.
+/// Returns the profiles that were added or updated.
+pub fn add_profiles(
+    profiles: &mut Vec<Profile>,
+    imported: Vec<Profile>,
+    policy: DuplicatePolicy,
+) -> (Vec<Profile>, ImportCounts) {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (i, profile) in profiles.iter().enumerate() {
+        if let Some(identity) = profile_identity(profile) {
+            index.entry(identity).or_insert(i);
+        }
+    }
+
+    let mut changed = Vec::new();
+    let mut counts = ImportCounts::default();
+    for profile in imported {
+        let identity = profile_identity(&profile);
+        let existing = identity.as_ref().and_then(|id| index.get(id)).copied();
+        match (existing, policy) {
+            (Some(_), DuplicatePolicy::Skip) => counts.duplicates += 1,
+            (Some(i), DuplicatePolicy::Replace) => {
+                replace_profile(&mut profiles[i], profile);
+                changed.push(profiles[i].clone());
+                counts.updated += 1;
+            }
+            _ => {
+                if let Some(identity) = identity {
+                    index.entry(identity).or_insert(profiles.len());
+                }
+                changed.push(profile.clone());
+                profiles.push(profile);
+                counts.added += 1;
+            }
+        }
+    }
+    (changed, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_profile;
+    use serde_json::json;
+
+    #[test]
+    fn identity_ignores_name_and_transport_options() {
+        let a = test_profile("a", "trojan://secret@Example.com:443?sni=x.example#First");
+        let b = test_profile("b", "trojan://secret@example.com:443?type=ws#Second");
+        assert_eq!(profile_identity(&a), profile_identity(&b));
+
+        let mut outbound = test_profile("c", "");
+        outbound.outbound = Some(json!({
+            "type": "trojan",
+            "tag": "c",
+            "server": "example.com",
+            "server_port": 443,
+            "password": "secret"
+        }));
+        assert_eq!(profile_identity(&outbound), profile_identity(&a));
+    }
+
+    #[test]
+    fn policies() {
+        let existing = vec![test_profile("old", "trojan://secret@example.com:443#Old")];
+        let imported = || {
+            vec![
+                test_profile("dup", "trojan://secret@example.com:443#New"),
+                test_profile("other", "trojan://secret@other.example:443#Other"),
+            ]
+        };
+
+        let mut profiles = existing.clone();
+        let (_, counts) = add_profiles(&mut profiles, imported(), DuplicatePolicy::Skip);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (1, 0, 1));
+        assert_eq!(profiles.len(), 2);
+
+        let mut profiles = existing.clone();
+        let (_, counts) = add_profiles(&mut profiles, imported(), DuplicatePolicy::Replace);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (1, 1, 0));
+        assert_eq!(profiles[0].id, "old");
+        assert_eq!(profiles[0].name, "dup");
+
+        let mut profiles = existing;
+        let (_, counts) = add_profiles(&mut profiles, imported(), DuplicatePolicy::KeepBoth);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (2, 0, 0));
+        assert_eq!(profiles.len(), 3);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+use crate::Profile;
+
+/// Follows `Profile::detour` from `start`: the profile itself, then the one it connects
+/// through, and so on up to the entry server. Fails on a missing hop or a cycle.
+pub fn detour_chain<'a>(
+    profiles: &'a [Profile],
+    start: &'a Profile,
+) -> Result<Vec<&'a Profile>, String> {
+    let mut chain = vec![start];
+    let mut seen = HashSet::from([start.id.as_str()]);
+    let mut current = start;
+    while let Some(next_id) = &current.detour {
+        let next = profiles.iter().find(|p| &p.id == next_id).ok_or_else(|| {
+            format!(
+                "{} connects via a profile that no longer exists",
+                current.name
+            )
+        })?;
+        if !seen.insert(next.id.as_str()) {
+            return Err(format!(
+                "{} connects via itself through {}",
+                next.name, current.name
+            ));
+        }
+        chain.push(next);
+        current = next;
+    }
+    Ok(chain)
+}
+
+/// Profiles that went through a profile that is gone connect directly again.
+pub fn clear_dangling(profiles: &mut [Profile]) {
+    let ids: HashSet<String> = profiles.iter().map(|p| p.id.clone()).collect();
+    for profile in profiles.iter_mut() {
+        if profile.detour.as_ref().is_some_and(|d| !ids.contains(d)) {
+            profile.detour = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_profile;
+
+    fn profile(id: &str, detour: Option<&str>) -> Profile {
+        Profile {
+            detour: detour.map(|d| d.to_string()),
+            ..test_profile(id, &format!("trojan://p@{}.example:443", id))
+        }
+    }
+
+    #[test]
+    fn follows_chains_and_rejects_cycles() {
+        let profiles = vec![
+            profile("exit", Some("middle")),
+            profile("middle", Some("entry")),
+            profile("entry", None),
+        ];
+        let ids: Vec<&str> = detour_chain(&profiles, &profiles[0])
+            .unwrap()
+            .iter()
+            .map(|p| p.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["exit", "middle", "entry"]);
+
+        let looped = vec![
+            profile("a", Some("b")),
+            profile("b", Some("c")),
+            profile("c", Some("a")),
+        ];
+        assert!(detour_chain(&looped, &looped[0]).is_err());
+        let own = vec![profile("a", Some("a"))];
+        assert!(detour_chain(&own, &own[0]).is_err());
+        let dangling = vec![profile("a", Some("gone"))];
+        assert!(detour_chain(&dangling, &dangling[0]).is_err());
+    }
+
+    #[test]
+    fn clears_only_missing_hops() {
+        let mut profiles = vec![
+            profile("a", Some("gone")),
+            profile("b", Some("c")),
+            profile("c", None),
+        ];
+        clear_dangling(&mut profiles);
+        assert_eq!(profiles[0].detour, None);
+        assert_eq!(profiles[1].detour.as_deref(), Some("c"));
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// A user-defined group of profiles, selectable like a single profile.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProfileGroup {
+    pub id: String,
+    pub name: String,
+    pub kind: GroupKind,
+    /// Members in priority order, which matters for fallback.
+    pub profile_ids: Vec<String>,
+    /// Used by fallback groups to check whether a member is healthy.
+    #[serde(default = "default_test_url")]
+    pub test_url: String,
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupKind {
+    /// Use the first member that passes the health check.
+    Fallback,
+    /// Spread connections across all members in turn.
+    LoadBalance,
+}
+
+pub fn default_test_url() -> String {
+    "https://www.gstatic.com/generate_204".to_string()
+}
+
+pub fn default_interval_secs() -> u64 {
+    30
+}
+
+/// What the groups add to the tunnel config.
+#[derive(Debug, Default)]
+pub struct GroupConfig {
+    pub outbounds: Vec<Value>,
+    pub fallbacks: Vec<FallbackWatch>,
+    pub balancers: Vec<BalanceWatch>,
+}
+
+/// A fallback group the app keeps pointed at its first healthy member.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallbackWatch {
+    pub name: String,
+    pub group_tag: String,
+    pub members: Vec<String>,
+    pub test_url: String,
+    pub interval_secs: u64,
+}
+
+/// A load-balancing group the app moves to the next member for each new connection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceWatch {
+    pub name: String,
+    pub group_tag: String,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Probe {
+    Healthy(usize),
+    AllFailed,
+}
+
+/// Decides from health checks given in priority order, `None` while still pending.
+/// Returns as soon as the first healthy member is known, without waiting for the rest.
+pub fn decide(results: &[Option<bool>]) -> Option<Probe> {
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            Some(true) => return Some(Probe::Healthy(i)),
+            Some(false) => continue,
+            None => return None,
+        }
+    }
+    Some(Probe::AllFailed)
+}
+
+/// Counts the connections in a clash API `/connections` reply that went through
+/// `group_tag` and are not in `seen`. `seen` is left holding the current ids.
+pub fn count_new_connections(
+    connections: &Value,
+    group_tag: &str,
+    seen: &mut HashSet<String>,
+) -> usize {
+    let mut current = HashSet::new();
+    let mut new = 0;
+    for connection in connections["connections"].as_array().into_iter().flatten() {
+        let through_group = connection["chains"]
+            .as_array()
+            .is_some_and(|chains| chains.iter().any(|c| c == group_tag));
+        let Some(id) = connection["id"].as_str() else {
+            continue;
+        };
+        if !through_group {
+            continue;
+        }
+        if !seen.contains(id) {
+            new += 1;
+        }
+        current.insert(id.to_string());
+    }
+    *seen = current;
+    new
+}
+
+impl ProfileGroup {
+    pub fn tag(&self) -> String {
+        format!("group-{}", self.id)
+    }
+
+    /// Adds the sing-box side of the group. sing-box has neither a fallback nor a
+    /// per-connection balancer outbound, so both kinds are a selector the app drives
+    /// through the clash API. `member_tags` must not be empty.
+    pub fn build(&self, member_tags: Vec<String>, config: &mut GroupConfig) {
+        let tag = self.tag();
+        config.outbounds.push(json!({
+            "type": "selector",
+            "tag": tag,
+            "outbounds": member_tags,
+            "default": member_tags[0]
+        }));
+        match self.kind {
+            GroupKind::Fallback => {
+                config.fallbacks.push(FallbackWatch {
+                    name: self.name.clone(),
+                    group_tag: tag,
+                    members: member_tags,
+                    test_url: self.test_url.clone(),
+                    interval_secs: self.interval_secs.max(5),
+                });
+            }
+            GroupKind::LoadBalance => {
+                config.balancers.push(BalanceWatch {
+                    name: self.name.clone(),
+                    group_tag: tag,
+                    members: member_tags,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(kind: GroupKind) -> ProfileGroup {
+        ProfileGroup {
+            id: "g".to_string(),
+            name: "Group".to_string(),
+            kind,
+            profile_ids: vec!["a".to_string(), "b".to_string()],
+            test_url: default_test_url(),
+            interval_secs: default_interval_secs(),
+        }
+    }
+
+    #[test]
+    fn load_balance_counts_new_connections() {
+        let mut config = GroupConfig::default();
+        let members = vec!["profile-a".to_string(), "profile-b".to_string()];
+        group(GroupKind::LoadBalance).build(members, &mut config);
+
+        assert_eq!(config.outbounds[0]["type"], "selector");
+        assert_eq!(config.balancers[0].group_tag, "group-g");
+        assert!(config.fallbacks.is_empty());
+
+        let mut seen = HashSet::new();
+        let first = json!({"connections": [
+            {"id": "1", "chains": ["profile-a", "group-g", "proxy"]},
+            {"id": "2", "chains": ["direct"]}
+        ]});
+        assert_eq!(count_new_connections(&first, "group-g", &mut seen), 1);
+        assert_eq!(count_new_connections(&first, "group-g", &mut seen), 0);
+
+        let second = json!({"connections": [
+            {"id": "1", "chains": ["profile-a", "group-g", "proxy"]},
+            {"id": "3", "chains": ["profile-b", "group-g", "proxy"]},
+            {"id": "4", "chains": ["profile-b", "group-g", "proxy"]}
+        ]});
+        assert_eq!(count_new_connections(&second, "group-g", &mut seen), 2);
+        assert_eq!(
+            count_new_connections(&json!({"connections": null}), "group-g", &mut seen),
+            0
+        );
+        assert!(seen.is_empty());
+    }
+
+    #[test]
+    fn fallback_prefers_first_healthy_member() {
+        let mut config = GroupConfig::default();
+        let members = vec!["profile-a".to_string(), "profile-b".to_string()];
+        group(GroupKind::Fallback).build(members, &mut config);
+
+        assert_eq!(config.outbounds[0]["type"], "selector");
+        assert_eq!(config.outbounds[0]["default"], "profile-a");
+        assert_eq!(config.fallbacks[0].members.len(), 2);
+
+        assert_eq!(decide(&[Some(true), None]), Some(Probe::Healthy(0)));
+        assert_eq!(decide(&[None, Some(true)]), None);
+        assert_eq!(decide(&[Some(false), Some(true)]), Some(Probe::Healthy(1)));
+        assert_eq!(decide(&[Some(false), Some(false)]), Some(Probe::AllFailed));
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TestMode {
+    /// A real request through the profile, via the clash API delay endpoint.
+    #[default]
+    Url,
+    /// Only opens a TCP connection to the server (the entry server for chains).
+    Tcp,
+}
+
+/// The outcome of the last test, stored on the profile.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LatencyResult {
+    pub mode: TestMode,
+    /// Unix timestamp of the test.
+    pub tested_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LatencyResult {
+    pub fn new(mode: TestMode, tested_at: u64, outcome: Result<u64, String>) -> Self {
+        let (delay_ms, error) = match outcome {
+            Ok(delay) => (Some(delay), None),
+            Err(e) => (None, Some(e)),
+        };
+        LatencyResult {
+            mode,
+            tested_at,
+            delay_ms,
+            error,
+        }
+    }
+}
+
+/// Emitted as `profile-latency` as soon as a profile has been tested.
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileLatency {
+    pub profile_id: String,
+    #[serde(flatten)]
+    pub result: LatencyResult,
+}
+
+/// Reads `{"delay": 123}`, or the `{"message": ".."}` sing-box answers with on failure.
+pub fn parse_delay_response(body: &str) -> Result<u64, String> {
+    let value: Value = serde_json::from_str(body).map_err(|_| body.trim().to_string())?;
+    if let Some(delay) = value.get("delay").and_then(|d| d.as_u64()) {
+        return Ok(delay);
+    }
+    Err(value
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or("Test failed")
+        .to_string())
+}
+
+/// Where TCP mode connects for an entry outbound. UDP-only protocols have nothing to
+/// connect to over TCP.
+pub fn tcp_target(outbound: &Value) -> Result<(String, u16), String> {
+    let kind = outbound["type"].as_str().unwrap_or_default();
+    if matches!(kind, "wireguard" | "hysteria" | "hysteria2" | "tuic") {
+        return Err(format!("{} runs over UDP, not supported in TCP mode", kind));
+    }
+    let server = outbound["server"].as_str().unwrap_or_default();
+    if server.is_empty() {
+        return Err("Profile has no server address".to_string());
+    }
+    let port = outbound["server_port"].as_u64().unwrap_or(0) as u16;
+    Ok((server.to_string(), port))
+}
+
+pub fn tcp_ping(server: &str, port: u16, timeout: Duration) -> Result<u64, String> {
+    let addr = (server, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {}", server))?;
+    let started = Instant::now();
+    TcpStream::connect_timeout(&addr, timeout).map_err(|e| e.to_string())?;
+    Ok(started.elapsed().as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn reads_delay_and_errors() {
+        assert_eq!(parse_delay_response(r#"{"delay":87}"#), Ok(87));
+        assert_eq!(
+            parse_delay_response(r#"{"message":"context deadline exceeded"}"#),
+            Err("context deadline exceeded".to_string())
+        );
+        assert!(parse_delay_response("not json").is_err());
+    }
+
+    #[test]
+    fn tcp_mode_skips_udp_protocols() {
+        let trojan =
+            serde_json::json!({ "type": "trojan", "server": "a.example", "server_port": 443 });
+        assert_eq!(tcp_target(&trojan), Ok(("a.example".to_string(), 443)));
+        for kind in ["wireguard", "hysteria2", "tuic"] {
+            let outbound =
+                serde_json::json!({ "type": kind, "server": "a.example", "server_port": 443 });
+            assert!(tcp_target(&outbound).unwrap_err().contains("TCP mode"));
+        }
+        assert!(tcp_target(&serde_json::json!({ "type": "socks" })).is_err());
+    }
+
+    #[test]
+    fn tcp_ping_connects_locally() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(tcp_ping("127.0.0.1", port, Duration::from_secs(1)).is_ok());
+
+        drop(listener);
+        assert!(tcp_ping("127.0.0.1", port, Duration::from_secs(1)).is_err());
+    }
+}
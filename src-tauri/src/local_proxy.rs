@@ -0,0 +1,212 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// A short-lived sing-box instance exposing the first of `outbounds` as a local HTTP/SOCKS
+/// proxy; the rest are the hops it connects through.
+/// It only opens a mixed inbound on loopback, so unlike the tunnel it needs no privileges.
+/// The process is killed when the value is dropped.
+pub struct LocalProxy {
+    child: Child,
+    config_path: PathBuf,
+    pub port: u16,
+    /// Port of the clash API, when started with `start_with_api`.
+    pub api_port: Option<u16>,
+}
+
+pub fn free_port() -> Result<u16, String> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    Ok(port)
+}
+
+fn core_command(singbox_path: &str) -> Command {
+    let command = Command::new(singbox_path);
+    #[cfg(target_os = "windows")]
+    let command = {
+        use std::os::windows::process::CommandExt;
+        let mut command = command;
+        // CREATE_NO_WINDOW, otherwise every helper flashes a console
+        command.creation_flags(0x08000000);
+        command
+    };
+    command
+}
+
+/// Runs `sing-box check` on a config file and returns its complaint when it fails.
+pub fn check_config(singbox_path: &str, config_path: &Path) -> Result<(), String> {
+    let output = core_command(singbox_path)
+        .arg("check")
+        .arg("-c")
+        .arg(config_path)
+        .output()
+        .map_err(|e| format!("Failed to start sing-box: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+impl LocalProxy {
+    pub fn start(
+        singbox_path: &str,
+        outbounds: Vec<Value>,
+        work_dir: &Path,
+    ) -> Result<Self, String> {
+        Self::launch(singbox_path, outbounds, work_dir, None)
+    }
+
+    /// Also exposes the clash API on loopback, e.g. to run delay tests on every outbound.
+    pub fn start_with_api(
+        singbox_path: &str,
+        outbounds: Vec<Value>,
+        work_dir: &Path,
+    ) -> Result<Self, String> {
+        Self::launch(singbox_path, outbounds, work_dir, Some(free_port()?))
+    }
+
+    // Routes everything from the loopback inbound to the first outbound
+    fn config(
+        mut outbounds: Vec<Value>,
+        port: u16,
+        api_port: Option<u16>,
+    ) -> Result<Value, String> {
+        let target = outbounds
+            .first()
+            .map(|o| o["tag"].clone())
+            .ok_or("No outbound to proxy through")?;
+        outbounds.push(json!({ "type": "direct", "tag": "direct" }));
+
+        let mut config = json!({
+            "log": { "level": "warn" },
+            "inbounds": [{
+                "type": "mixed",
+                "tag": "mixed-in",
+                "listen": "127.0.0.1",
+                "listen_port": port
+            }],
+            "outbounds": outbounds,
+            "route": { "final": target }
+        });
+        if let Some(api_port) = api_port {
+            config["experimental"] = json!({
+                "clash_api": { "external_controller": format!("127.0.0.1:{}", api_port) }
+            });
+        }
+        Ok(config)
+    }
+
+    fn launch(
+        singbox_path: &str,
+        outbounds: Vec<Value>,
+        work_dir: &Path,
+        api_port: Option<u16>,
+    ) -> Result<Self, String> {
+        let port = free_port()?;
+        let config = Self::config(outbounds, port, api_port)?;
+
+        let _ = fs::create_dir_all(work_dir);
+        let config_path = work_dir.join(format!("local-proxy-{}.json", port));
+        fs::write(&config_path, config.to_string()).map_err(|e| e.to_string())?;
+
+        let child = core_command(singbox_path)
+            .arg("run")
+            .arg("-c")
+            .arg(&config_path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start sing-box: {}", e))?;
+
+        let mut proxy = LocalProxy {
+            child,
+            config_path,
+            port,
+            api_port,
+        };
+        proxy.wait_until_ready(Duration::from_secs(5))?;
+        Ok(proxy)
+    }
+
+    fn wait_until_ready(&mut self, timeout: Duration) -> Result<(), String> {
+        let ports = [Some(self.port), self.api_port];
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Err(format!("sing-box exited early ({})", status));
+            }
+            let open = |port: &u16| {
+                let addr = SocketAddr::from(([127, 0, 0, 1], *port));
+                TcpStream::connect_timeout(&addr, Duration::from_millis(100)).is_ok()
+            };
+            if ports.iter().flatten().all(open) {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Err("sing-box did not open its local proxy in time".to_string())
+    }
+
+    /// Runs `sing-box check` on the config `start` would use, without starting anything.
+    pub fn check(singbox_path: &str, outbounds: Vec<Value>, work_dir: &Path) -> Result<(), String> {
+        let port = free_port()?;
+        let config = Self::config(outbounds, port, None)?;
+        let _ = fs::create_dir_all(work_dir);
+        let config_path = work_dir.join(format!("local-proxy-check-{}.json", port));
+        fs::write(&config_path, config.to_string()).map_err(|e| e.to_string())?;
+
+        let checked = check_config(singbox_path, &config_path);
+        let _ = fs::remove_file(&config_path);
+        checked
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+}
+
+impl Drop for LocalProxy {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_file(&self.config_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxies_the_first_outbound() {
+        let outbounds = vec![
+            json!({ "type": "trojan", "tag": "profile-exit", "detour": "profile-entry" }),
+            json!({ "type": "trojan", "tag": "profile-entry" }),
+        ];
+        let config = LocalProxy::config(outbounds, 10800, Some(10900)).unwrap();
+        assert_eq!(config["inbounds"][0]["listen"], "127.0.0.1");
+        assert_eq!(config["inbounds"][0]["listen_port"], 10800);
+        assert_eq!(config["route"]["final"], "profile-exit");
+        assert_eq!(config["outbounds"][2]["type"], "direct");
+        assert_eq!(
+            config["experimental"]["clash_api"]["external_controller"],
+            "127.0.0.1:10900"
+        );
+
+        let config = LocalProxy::config(vec![json!({ "tag": "a" })], 10800, None).unwrap();
+        assert!(config.get("experimental").is_none());
+        assert!(LocalProxy::config(Vec::new(), 10800, None).is_err());
+    }
+
+    #[test]
+    fn missing_core_fails_to_start() {
+        let work_dir = std::env::temp_dir().join("nugget-local-proxy-test");
+        let outbounds = vec![json!({ "type": "direct", "tag": "out" })];
+        assert!(LocalProxy::check("/nonexistent/sing-box", outbounds.clone(), &work_dir).is_err());
+        assert!(LocalProxy::start("/nonexistent/sing-box", outbounds, &work_dir).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use serde_json::Value;
+
+// Outbound types that actually carry traffic to a remote server; everything else
+// (direct, block, dns, selector, urltest) only makes sense inside the original config.
+const PROXY_TYPES: &[&str] = &[
+    "shadowsocks",
+    "vmess",
+    "vless",
+    "trojan",
+    "hysteria",
+    "hysteria2",
+    "tuic",
+    "wireguard",
+    "socks",
+    "http",
+    "shadowtls",
+    "ssh",
+    "anytls",
+];
+
+/// Returns the proxy outbounds of a sing-box config, or of a bare `outbounds` array.
+pub fn find_outbounds(text: &str) -> Option<Vec<Value>> {
+    let trimmed = text.trim_start();
+    if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+        return None;
+    }
+    let doc: Value = serde_json::from_str(text).ok()?;
+    let outbounds = match &doc {
+        Value::Object(config) => config.get("outbounds")?.as_array()?,
+        Value::Array(outbounds) => outbounds,
+        _ => return None,
+    };
+
+    Some(
+        outbounds
+            .iter()
+            .filter(|o| {
+                o.get("type")
+                    .and_then(|t| t.as_str())
+                    .map(|t| PROXY_TYPES.contains(&t))
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .map(|mut outbound| {
+                // Detours point at tags from the original config that will not exist in ours
+                if let Some(fields) = outbound.as_object_mut() {
+                    fields.remove("detour");
+                }
+                outbound
+            })
+            .collect(),
+    )
+}
+
+/// Maps a sing-box outbound type onto the protocol label used by `Profile`.
+pub fn protocol_label(outbound: &Value) -> String {
+    match outbound.get("type").and_then(|t| t.as_str()) {
+        Some("shadowsocks") => "ss".to_string(),
+        Some(other) => other.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_proxy_outbounds_without_detours() {
+        let config = json!({
+            "log": { "level": "info" },
+            "outbounds": [
+                { "type": "selector", "tag": "select", "outbounds": ["hk", "jp"] },
+                {
+                    "type": "vless",
+                    "tag": "hk",
+                    "server": "198.51.100.10",
+                    "server_port": 443,
+                    "uuid": "5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718",
+                    "detour": "relay"
+                },
+                {
+                    "type": "shadowsocks",
+                    "tag": "jp",
+                    "server": "198.51.100.11",
+                    "server_port": 8388,
+                    "method": "aes-256-gcm",
+                    "password": "secret"
+                },
+                { "type": "direct", "tag": "direct" },
+                { "type": "block", "tag": "block" },
+                { "type": "dns", "tag": "dns-out" }
+            ]
+        });
+
+        let outbounds = find_outbounds(&config.to_string()).unwrap();
+        let tags: Vec<&str> = outbounds
+            .iter()
+            .map(|o| o["tag"].as_str().unwrap())
+            .collect();
+        assert_eq!(tags, vec!["hk", "jp"]);
+        assert!(outbounds[0].get("detour").is_none());
+        assert_eq!(outbounds[0]["uuid"], "5f1e3b2a-9c4d-4e8f-a1b2-c3d4e5f60718");
+        assert_eq!(protocol_label(&outbounds[0]), "vless");
+        assert_eq!(protocol_label(&outbounds[1]), "ss");
+
+        // A bare outbounds array works as well
+        let list = json!([{ "type": "trojan", "tag": "t", "server": "198.51.100.12" }]);
+        assert_eq!(find_outbounds(&list.to_string()).unwrap().len(), 1);
+
+        assert!(find_outbounds("vless://abc@198.51.100.10:443").is_none());
+        assert!(find_outbounds(r#"{"version": 1, "servers": []}"#).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::Value;
+
+/// Outline dynamic access keys are fetched over https from the same host and path.
+pub fn ssconf_to_https(link: &str) -> Result<String, String> {
+    let rest = link
+        .strip_prefix("ssconf://")
+        .ok_or("Not an ssconf:// link")?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    if rest.is_empty() {
+        return Err("ssconf:// link has no host".to_string());
+    }
+    Ok(format!("https://{}", rest))
+}
+
+fn server_to_link(server: &Value) -> Option<String> {
+    let host = server.get("server")?.as_str()?;
+    let port = match server.get("server_port")? {
+        Value::Number(n) => n.as_u64()?,
+        Value::String(s) => s.parse().ok()?,
+        _ => return None,
+    };
+    let method = server.get("method")?.as_str()?;
+    let password = server.get("password")?.as_str()?;
+    let name = server
+        .get("remarks")
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty())
+        .unwrap_or(host);
+
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let user_info = general_purpose::URL_SAFE_NO_PAD.encode(format!("{}:{}", method, password));
+
+    let plugin = server
+        .get("plugin")
+        .and_then(|p| p.as_str())
+        .filter(|p| !p.is_empty());
+    let query = match plugin {
+        Some(plugin) => {
+            let opts = server
+                .get("plugin_opts")
+                .and_then(|o| o.as_str())
+                .unwrap_or("");
+            let plugin = if opts.is_empty() {
+                plugin.to_string()
+            } else {
+                format!("{};{}", plugin, opts)
+            };
+            format!("?plugin={}", urlencoding::encode(&plugin))
+        }
+        None => String::new(),
+    };
+
+    Some(format!(
+        "ss://{}@{}{}#{}",
+        user_info,
+        authority,
+        query,
+        urlencoding::encode(name)
+    ))
+}
+
+/// Converts a SIP008 document (or Outline's single-server variant) into `ss://` links.
+pub fn find_servers(text: &str) -> Option<Vec<String>> {
+    if !text.trim_start().starts_with('{') {
+        return None;
+    }
+    let doc: Value = serde_json::from_str(text).ok()?;
+
+    if let Some(servers) = doc.get("servers").and_then(|s| s.as_array()) {
+        return Some(servers.iter().filter_map(server_to_link).collect());
+    }
+    if doc.get("server").is_some() && doc.get("method").is_some() {
+        return Some(server_to_link(&doc).into_iter().collect());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::share_link::ShareLink;
+
+    #[test]
+    fn converts_servers_with_plugins_and_sip022_keys() {
+        let links = find_servers(
+            r#"{
+                "version": 1,
+                "servers": [{
+                    "id": "27b8a625-4f4b-4428-9f0f-8a2317db7c79",
+                    "remarks": "Obfs",
+                    "server": "198.51.100.8",
+                    "server_port": 8388,
+                    "password": "secret",
+                    "method": "chacha20-ietf-poly1305",
+                    "plugin": "obfs-local",
+                    "plugin_opts": "obfs=tls;obfs-host=bing.com"
+                }, {
+                    "server": "2001:db8::8",
+                    "server_port": "443",
+                    "password": "AAECAwQFBgcICQoLDA0ODw==",
+                    "method": "2022-blake3-aes-128-gcm"
+                }, {
+                    "server": "198.51.100.9",
+                    "method": "aes-256-gcm"
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(links.len(), 2);
+
+        let settings = Default::default();
+        let obfs = ShareLink::parse_checked(&links[0]).unwrap();
+        assert_eq!(obfs.name(), "Obfs");
+        let obfs = obfs.to_outbound(&settings).unwrap();
+        assert_eq!(obfs["method"], "chacha20-ietf-poly1305");
+        assert_eq!(obfs["plugin"], "obfs-local");
+        assert_eq!(obfs["plugin_opts"], "obfs=tls;obfs-host=bing.com");
+
+        let blake3 = ShareLink::parse_checked(&links[1])
+            .unwrap()
+            .to_outbound(&settings)
+            .unwrap();
+        assert_eq!(blake3["server"], "2001:db8::8");
+        assert_eq!(blake3["server_port"], 443);
+        assert_eq!(blake3["password"], "AAECAwQFBgcICQoLDA0ODw==");
+    }
+}
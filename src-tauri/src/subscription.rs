@@ -0,0 +1,632 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dedup::{profile_identity, replace_profile, DuplicatePolicy, ImportCounts};
+use crate::share_link::ShareLink;
+use crate::Profile;
+
+/// A subscription URL and the profiles it last produced (linked via `Profile::subscription_id`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Subscription {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    /// Unix timestamp of the last successful refresh.
+    #[serde(default)]
+    pub last_updated: Option<u64>,
+    /// Hours between automatic refreshes, 0 turns them off.
+    #[serde(default)]
+    pub interval_hours: u32,
+    /// Take the interval from the provider's `profile-update-interval` header.
+    #[serde(default)]
+    pub follow_provider_interval: bool,
+    /// Quota reported in the last `subscription-userinfo` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<SubscriptionUsage>,
+    #[serde(default)]
+    pub fetch: FetchOptions,
+    #[serde(default)]
+    pub rules: ProfileRules,
+}
+
+/// Filters and renames applied to a subscription's servers before they are stored.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ProfileRules {
+    /// When not empty, only servers matching at least one of these are kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<ProfileFilter>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<ProfileFilter>,
+    /// Applied to the name in order, after filtering.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rename: Vec<RenameRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+    #[default]
+    Name,
+    Protocol,
+    Server,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ProfileFilter {
+    #[serde(default)]
+    pub field: FilterField,
+    pub pattern: String,
+}
+
+/// Regex replacement on the profile name; `$1` style groups are supported.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RenameRule {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid pattern \"{}\": {}", pattern, e))
+}
+
+fn profile_server(profile: &Profile) -> String {
+    match &profile.outbound {
+        Some(outbound) => outbound
+            .get("server")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        None => ShareLink::parse(&profile.config_link)
+            .map(|link| link.server().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+impl ProfileRules {
+    /// Checks that every pattern compiles, so bad rules are rejected when they are saved.
+    pub fn validate(&self) -> Result<(), String> {
+        let filters = self.include.iter().chain(&self.exclude);
+        for pattern in filters
+            .map(|f| &f.pattern)
+            .chain(self.rename.iter().map(|r| &r.pattern))
+        {
+            compile(pattern)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the profiles that pass the filters, renamed, and how many were dropped.
+    pub fn apply(&self, profiles: Vec<Profile>) -> Result<(Vec<Profile>, usize), String> {
+        let filters = |filters: &[ProfileFilter]| -> Result<Vec<(FilterField, Regex)>, String> {
+            filters
+                .iter()
+                .map(|f| Ok((f.field, compile(&f.pattern)?)))
+                .collect()
+        };
+        let include = filters(&self.include)?;
+        let exclude = filters(&self.exclude)?;
+        let rename = self
+            .rename
+            .iter()
+            .map(|r| Ok((compile(&r.pattern)?, r.replacement.as_str())))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let total = profiles.len();
+        let mut kept = Vec::new();
+        for mut profile in profiles {
+            let server = profile_server(&profile);
+            let matches = |(field, regex): &(FilterField, Regex)| {
+                regex.is_match(match field {
+                    FilterField::Name => &profile.name,
+                    FilterField::Protocol => &profile.protocol,
+                    FilterField::Server => &server,
+                })
+            };
+            if (!include.is_empty() && !include.iter().any(matches)) || exclude.iter().any(matches)
+            {
+                continue;
+            }
+
+            for (regex, replacement) in &rename {
+                profile.name = regex.replace_all(&profile.name, *replacement).into_owned();
+            }
+            if let Some(prefix) = &self.prefix {
+                profile.name = format!("{}{}", prefix, profile.name);
+            }
+            profile.name = profile.name.trim().to_string();
+            kept.push(profile);
+        }
+        let filtered = total - kept.len();
+        Ok((kept, filtered))
+    }
+}
+
+/// How the subscription URL is requested.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct FetchOptions {
+    /// Overrides the default User-Agent, which offers panels sing-box JSON or Clash YAML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<SubscriptionAuth>,
+    #[serde(default)]
+    pub route: FetchRoute,
+}
+
+/// Where the subscription request goes out. Anything but `Direct` falls back to a
+/// direct request when the proxy is unavailable or the proxied request fails.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FetchRoute {
+    #[default]
+    Direct,
+    /// Through the running tunnel's local mixed inbound.
+    Tunnel,
+    /// Through a temporary sing-box instance using this profile.
+    Profile { profile_id: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SubscriptionAuth {
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+}
+
+/// Traffic quota and expiry as reported by the provider, in bytes and unix seconds.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SubscriptionUsage {
+    pub upload: u64,
+    pub download: u64,
+    /// 0 when the plan has no traffic limit.
+    pub total: u64,
+    pub remaining: Option<u64>,
+    pub expire: Option<u64>,
+}
+
+impl SubscriptionUsage {
+    /// Parses `upload=..; download=..; total=..; expire=..`. Missing keys count as 0.
+    pub fn parse_header(header: &str) -> Option<Self> {
+        let mut usage = SubscriptionUsage::default();
+        let mut found = false;
+        for pair in header.split(';') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            // Some panels print large counters in float notation
+            let value = value.trim();
+            let Some(value) = value
+                .parse::<u64>()
+                .ok()
+                .or_else(|| value.parse::<f64>().ok().map(|v| v.max(0.0) as u64))
+            else {
+                continue;
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "upload" => usage.upload = value,
+                "download" => usage.download = value,
+                "total" => usage.total = value,
+                "expire" => usage.expire = Some(value).filter(|e| *e > 0),
+                _ => continue,
+            }
+            found = true;
+        }
+        if !found {
+            return None;
+        }
+        if usage.total > 0 {
+            usage.remaining = Some(
+                usage
+                    .total
+                    .saturating_sub(usage.upload.saturating_add(usage.download)),
+            );
+        }
+        Some(usage)
+    }
+}
+
+/// Emitted as `subscription-alert` when a subscription is about to run out.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SubscriptionAlert {
+    pub subscription_id: String,
+    pub name: String,
+    /// "quota" or "expiry".
+    pub kind: &'static str,
+    pub remaining: Option<u64>,
+    pub expire: Option<u64>,
+}
+
+impl Subscription {
+    pub fn alerts(
+        &self,
+        now: u64,
+        min_remaining: u64,
+        expiry_window: u64,
+    ) -> Vec<SubscriptionAlert> {
+        let Some(usage) = &self.usage else {
+            return Vec::new();
+        };
+        let alert = |kind| SubscriptionAlert {
+            subscription_id: self.id.clone(),
+            name: self.name.clone(),
+            kind,
+            remaining: usage.remaining,
+            expire: usage.expire,
+        };
+
+        let mut alerts = Vec::new();
+        if matches!(usage.remaining, Some(remaining) if remaining < min_remaining) {
+            alerts.push(alert("quota"));
+        }
+        if matches!(usage.expire, Some(expire) if expire <= now.saturating_add(expiry_window)) {
+            alerts.push(alert("expiry"));
+        }
+        alerts
+    }
+
+    /// Alerts whose threshold was crossed since `previous`, the state before this refresh,
+    /// so a low quota or near expiry is reported once rather than on every refresh.
+    pub fn new_alerts(
+        &self,
+        previous: &Subscription,
+        now: u64,
+        min_remaining: u64,
+        expiry_window: u64,
+    ) -> Vec<SubscriptionAlert> {
+        let before = previous.last_updated.unwrap_or(0);
+        let already: Vec<&str> = previous
+            .alerts(before, min_remaining, expiry_window)
+            .iter()
+            .map(|a| a.kind)
+            .collect();
+        self.alerts(now, min_remaining, expiry_window)
+            .into_iter()
+            .filter(|a| !already.contains(&a.kind))
+            .collect()
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        if self.interval_hours == 0 {
+            return false;
+        }
+        match self.last_updated {
+            Some(last) => now >= last + self.interval_hours as u64 * 3600,
+            None => true,
+        }
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Replaces the profiles owned by `subscription_id` with `fresh`. Servers that were already
+/// present keep their id and usage counters, and the new list takes the place of the old one.
+/// Repeated servers within `fresh` are dropped unless the policy keeps both.
+pub fn merge_profiles(
+    profiles: &mut Vec<Profile>,
+    subscription_id: &str,
+    fresh: Vec<Profile>,
+    policy: DuplicatePolicy,
+) -> ImportCounts {
+    let owned = |p: &Profile| p.subscription_id.as_deref() == Some(subscription_id);
+    let position = profiles.iter().position(owned).unwrap_or(profiles.len());
+    let key = |p: &Profile| profile_identity(p).unwrap_or_else(|| p.config_link.clone());
+
+    let mut previous: HashMap<String, Profile> = HashMap::new();
+    for profile in profiles.iter().filter(|p| owned(p)) {
+        previous
+            .entry(key(profile))
+            .or_insert_with(|| profile.clone());
+    }
+    profiles.retain(|p| !owned(p));
+    // Servers that are already in the list from elsewhere go through the duplicate policy
+    let mut others: HashMap<String, usize> = HashMap::new();
+    for (i, profile) in profiles.iter().enumerate() {
+        if let Some(identity) = profile_identity(profile) {
+            others.entry(identity).or_insert(i);
+        }
+    }
+
+    let mut counts = ImportCounts::default();
+    let mut seen = HashSet::new();
+    let mut merged = Vec::new();
+    for mut profile in fresh {
+        let identity = key(&profile);
+        if !seen.insert(identity.clone()) && policy != DuplicatePolicy::KeepBoth {
+            counts.duplicates += 1;
+            continue;
+        }
+        profile.subscription_id = Some(subscription_id.to_string());
+        match previous.remove(&identity) {
+            Some(old) => {
+                profile.id = old.id;
+                profile.total_up = old.total_up;
+                profile.total_down = old.total_down;
+                profile.detour = old.detour;
+                profile.latency = old.latency;
+                counts.updated += 1;
+            }
+            None => match (others.get(&identity), policy) {
+                (Some(_), DuplicatePolicy::Skip) => {
+                    counts.duplicates += 1;
+                    continue;
+                }
+                (Some(&i), DuplicatePolicy::Replace) => {
+                    replace_profile(&mut profiles[i], profile);
+                    counts.updated += 1;
+                    continue;
+                }
+                _ => counts.added += 1,
+            },
+        }
+        merged.push(profile);
+    }
+    profiles.splice(position..position, merged);
+    // Servers the provider dropped may have been hops of other profiles
+    crate::detour::clear_dangling(profiles);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_profile;
+
+    fn profile(id: &str, name: &str, link: &str, subscription_id: Option<&str>) -> Profile {
+        Profile {
+            name: name.to_string(),
+            subscription_id: subscription_id.map(|s| s.to_string()),
+            ..test_profile(id, &format!("{}#{}", link, name))
+        }
+    }
+
+    #[test]
+    fn refresh_keeps_usage_of_unchanged_servers() {
+        let mut kept = profile("a", "Old name", "trojan://p@a.example:443", Some("sub"));
+        kept.total_up = Some(10);
+        kept.total_down = Some(20);
+        let mut profiles = vec![
+            profile("manual", "Manual", "trojan://p@m.example:443", None),
+            kept,
+            profile("b", "Gone", "trojan://p@b.example:443", Some("sub")),
+        ];
+
+        let fresh = vec![
+            profile("new-1", "New name", "trojan://p@a.example:443", None),
+            profile("new-2", "Added", "trojan://p@c.example:443", None),
+        ];
+        let counts = merge_profiles(&mut profiles, "sub", fresh, DuplicatePolicy::Skip);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (1, 1, 0));
+
+        let ids: Vec<&str> = profiles.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, vec!["manual", "a", "new-2"]);
+        assert_eq!(profiles[1].name, "New name");
+        assert_eq!(profiles[1].total_up, Some(10));
+        assert_eq!(profiles[1].total_down, Some(20));
+        assert_eq!(profiles[2].subscription_id.as_deref(), Some("sub"));
+    }
+
+    #[test]
+    fn refresh_applies_policy_to_servers_from_elsewhere() {
+        let manual = || profile("manual", "Manual", "trojan://p@a.example:443", None);
+        let fresh = || vec![profile("new", "Fresh", "trojan://p@a.example:443", None)];
+
+        let mut profiles = vec![manual()];
+        let counts = merge_profiles(&mut profiles, "sub", fresh(), DuplicatePolicy::Skip);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (0, 0, 1));
+        assert_eq!(profiles.len(), 1);
+
+        let counts = merge_profiles(&mut profiles, "sub", fresh(), DuplicatePolicy::Replace);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (0, 1, 0));
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].id, "manual");
+        assert_eq!(profiles[0].name, "Fresh");
+
+        let mut profiles = vec![manual()];
+        let counts = merge_profiles(&mut profiles, "sub", fresh(), DuplicatePolicy::KeepBoth);
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (1, 0, 0));
+        assert_eq!(profiles.len(), 2);
+    }
+
+    #[test]
+    fn refresh_clears_detours_via_dropped_servers() {
+        let mut manual = profile("manual", "Manual", "trojan://p@m.example:443", None);
+        manual.detour = Some("b".to_string());
+        let mut profiles = vec![
+            manual,
+            profile("b", "Gone", "trojan://p@b.example:443", Some("sub")),
+        ];
+
+        let fresh = vec![profile("new", "New", "trojan://p@c.example:443", None)];
+        merge_profiles(&mut profiles, "sub", fresh, DuplicatePolicy::Skip);
+        assert_eq!(profiles[0].detour, None);
+    }
+
+    #[test]
+    fn interval_zero_never_refreshes() {
+        let mut subscription = Subscription {
+            id: "sub".to_string(),
+            name: "Provider".to_string(),
+            url: "https://example.com/sub".to_string(),
+            last_updated: None,
+            interval_hours: 0,
+            follow_provider_interval: false,
+            usage: None,
+            fetch: FetchOptions::default(),
+            rules: ProfileRules::default(),
+        };
+        assert!(!subscription.is_due(1_000_000));
+        subscription.interval_hours = 6;
+        assert!(subscription.is_due(1_000_000));
+        subscription.last_updated = Some(1_000_000);
+        assert!(!subscription.is_due(1_000_000 + 5 * 3600));
+        assert!(subscription.is_due(1_000_000 + 6 * 3600));
+    }
+
+    #[test]
+    fn parses_userinfo_header() {
+        let usage = SubscriptionUsage::parse_header(
+            "upload=1073741824; download=2147483648; total=10737418240; expire=1767225600",
+        )
+        .unwrap();
+        assert_eq!(usage.remaining, Some(7516192768));
+        assert_eq!(usage.expire, Some(1767225600));
+
+        let unlimited =
+            SubscriptionUsage::parse_header("upload=0; download=1.5e3; total=0").unwrap();
+        assert_eq!(unlimited.download, 1500);
+        assert_eq!(unlimited.remaining, None);
+        assert_eq!(unlimited.expire, None);
+
+        assert!(SubscriptionUsage::parse_header("garbage").is_none());
+    }
+
+    #[test]
+    fn alerts_once_per_crossing() {
+        let usage = |remaining, expire| SubscriptionUsage {
+            total: 100,
+            remaining: Some(remaining),
+            expire: Some(expire),
+            ..SubscriptionUsage::default()
+        };
+        let mut previous = Subscription {
+            id: "sub".to_string(),
+            name: "Provider".to_string(),
+            url: "https://example.com/sub".to_string(),
+            last_updated: Some(1000),
+            interval_hours: 1,
+            follow_provider_interval: false,
+            usage: Some(usage(90, 10_000)),
+            fetch: FetchOptions::default(),
+            rules: ProfileRules::default(),
+        };
+        let kinds = |current: &Subscription, previous: &Subscription, now| -> Vec<&str> {
+            current
+                .new_alerts(previous, now, 50, 1000)
+                .iter()
+                .map(|a| a.kind)
+                .collect()
+        };
+
+        let mut current = previous.clone();
+        current.last_updated = Some(2000);
+        current.usage = Some(usage(40, 10_000));
+        assert_eq!(kinds(&current, &previous, 2000), vec!["quota"]);
+
+        // Still low and now close to expiry: only the new crossing is reported
+        previous = current.clone();
+        current.last_updated = Some(9500);
+        current.usage = Some(usage(30, 10_000));
+        assert_eq!(kinds(&current, &previous, 9500), vec!["expiry"]);
+
+        previous = current.clone();
+        current.last_updated = Some(9600);
+        assert!(kinds(&current, &previous, 9600).is_empty());
+    }
+
+    #[test]
+    fn huge_counters_do_not_overflow() {
+        let usage = SubscriptionUsage::parse_header(
+            "upload=18446744073709551615; download=1; total=10737418240",
+        )
+        .unwrap();
+        assert_eq!(usage.remaining, Some(0));
+
+        let floats =
+            SubscriptionUsage::parse_header("upload=1e30; download=1e30; total=1e30").unwrap();
+        assert_eq!(floats.upload, u64::MAX);
+        assert_eq!(floats.remaining, Some(0));
+
+        let subscription = Subscription {
+            id: "sub".to_string(),
+            name: "Provider".to_string(),
+            url: "https://example.com/sub".to_string(),
+            last_updated: None,
+            interval_hours: 0,
+            follow_provider_interval: false,
+            usage: Some(SubscriptionUsage {
+                expire: Some(u64::MAX),
+                ..usage
+            }),
+            fetch: FetchOptions::default(),
+            rules: ProfileRules::default(),
+        };
+        let kinds: Vec<&str> = subscription
+            .alerts(u64::MAX - 1, u64::MAX, u64::MAX)
+            .iter()
+            .map(|a| a.kind)
+            .collect();
+        assert_eq!(kinds, vec!["quota", "expiry"]);
+    }
+
+    #[test]
+    fn rules_filter_and_rename() {
+        let mut ss = profile("3", "🇩🇪 DE Frankfurt", "trojan://p@de.example:443", None);
+        ss.protocol = "ss".to_string();
+        let profiles = vec![
+            profile("1", "🇺🇸 US New York", "trojan://p@us.example:443", None),
+            profile(
+                "2",
+                "🇺🇸 US Info: expires soon",
+                "trojan://p@info.example:443",
+                None,
+            ),
+            ss,
+            profile("4", "🇯🇵 JP Tokyo", "trojan://p@jp.example:443", None),
+        ];
+        let rules = ProfileRules {
+            include: vec![
+                ProfileFilter {
+                    field: FilterField::Name,
+                    pattern: "US".to_string(),
+                },
+                ProfileFilter {
+                    field: FilterField::Protocol,
+                    pattern: "^ss$".to_string(),
+                },
+            ],
+            exclude: vec![ProfileFilter {
+                field: FilterField::Server,
+                pattern: r"^info\.".to_string(),
+            }],
+            rename: vec![RenameRule {
+                pattern: r"^\W+\s*".to_string(),
+                replacement: String::new(),
+            }],
+            prefix: Some("[P] ".to_string()),
+        };
+
+        let (kept, filtered) = rules.apply(profiles).unwrap();
+        assert_eq!(filtered, 2);
+        let names: Vec<&str> = kept.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["[P] US New York", "[P] DE Frankfurt"]);
+
+        let broken = ProfileRules {
+            rename: vec![RenameRule {
+                pattern: "(".to_string(),
+                replacement: String::new(),
+            }],
+            ..ProfileRules::default()
+        };
+        assert!(broken.validate().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::Profile;
+
+/// The "Auto" entry of the tunnel: a sing-box `urltest` group that keeps traffic on the
+/// fastest healthy server among its members.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AutoSelect {
+    /// Profiles to test. Empty means every profile, or every profile of `subscription_id`.
+    #[serde(default)]
+    pub profile_ids: Vec<String>,
+    #[serde(default)]
+    pub subscription_id: Option<String>,
+    #[serde(default = "default_url")]
+    pub url: String,
+    /// sing-box duration, e.g. "3m" or "1m30s". Checked by `check`.
+    #[serde(default = "default_interval")]
+    pub interval: String,
+    /// Milliseconds a new server has to be faster by before traffic moves to it.
+    #[serde(default = "default_tolerance")]
+    pub tolerance: u16,
+}
+
+fn default_url() -> String {
+    "https://www.gstatic.com/generate_204".to_string()
+}
+
+fn default_interval() -> String {
+    "3m".to_string()
+}
+
+fn default_tolerance() -> u16 {
+    50
+}
+
+impl Default for AutoSelect {
+    fn default() -> Self {
+        Self {
+            profile_ids: Vec::new(),
+            subscription_id: None,
+            url: default_url(),
+            interval: default_interval(),
+            tolerance: default_tolerance(),
+        }
+    }
+}
+
+impl AutoSelect {
+    /// Rejects an interval sing-box would refuse to start with.
+    pub fn check(&self) -> Result<(), String> {
+        let duration = Regex::new(r"^(\d+(\.\d+)?(ns|us|µs|ms|s|m|h))+$").unwrap();
+        let interval = self.interval.trim();
+        let nonzero = interval.chars().any(|c| ('1'..='9').contains(&c));
+        if !duration.is_match(interval) || !nonzero {
+            return Err(format!(
+                "Invalid Auto test interval {:?}, use a duration like \"3m\" or \"90s\"",
+                self.interval
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn members<'a>(&self, profiles: &'a [Profile]) -> Vec<&'a Profile> {
+        profiles
+            .iter()
+            .filter(|p| self.profile_ids.is_empty() || self.profile_ids.contains(&p.id))
+            .filter(|p| match &self.subscription_id {
+                Some(id) => p.subscription_id.as_ref() == Some(id),
+                None => true,
+            })
+            .collect()
+    }
+
+    pub fn outbound(&self, tag: &str, member_tags: Vec<String>) -> Value {
+        json!({
+            "type": "urltest",
+            "tag": tag,
+            "outbounds": member_tags,
+            "url": self.url,
+            "interval": self.interval.trim(),
+            "tolerance": self.tolerance
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_profile;
+
+    fn profile(id: &str, subscription_id: Option<&str>) -> Profile {
+        Profile {
+            subscription_id: subscription_id.map(|s| s.to_string()),
+            ..test_profile(id, &format!("trojan://p@{}.example:443", id))
+        }
+    }
+
+    #[test]
+    fn interval_must_be_a_duration() {
+        let with_interval = |interval: &str| AutoSelect {
+            interval: interval.to_string(),
+            ..AutoSelect::default()
+        };
+        for ok in ["3m", "90s", "1m30s", "1.5h", " 10m "] {
+            assert!(with_interval(ok).check().is_ok(), "{}", ok);
+        }
+        for bad in ["", "3", "three minutes", "3 m", "0s", "-1m", "5d"] {
+            assert!(with_interval(bad).check().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn members_by_id_or_subscription() {
+        let profiles = vec![
+            profile("a", None),
+            profile("b", Some("sub")),
+            profile("c", Some("sub")),
+        ];
+        let ids = |auto: &AutoSelect| -> Vec<String> {
+            auto.members(&profiles)
+                .iter()
+                .map(|p| p.id.clone())
+                .collect()
+        };
+
+        let mut auto = AutoSelect::default();
+        assert_eq!(ids(&auto), vec!["a", "b", "c"]);
+        auto.subscription_id = Some("sub".to_string());
+        assert_eq!(ids(&auto), vec!["b", "c"]);
+        auto.profile_ids = vec!["a".to_string(), "c".to_string()];
+        assert_eq!(ids(&auto), vec!["c"]);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// The subset of a wg-quick `.conf` file that sing-box can use.
+#[derive(Debug, Default)]
+pub struct WireGuardConfig {
+    pub private_key: String,
+    pub addresses: Vec<String>,
+    /// DNS servers, without the search domains wg-quick also accepts there.
+    pub dns: Vec<String>,
+    pub mtu: Option<u32>,
+    pub peer_public_key: String,
+    pub preshared_key: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub endpoint_host: String,
+    pub endpoint_port: u16,
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+fn split_endpoint(endpoint: &str) -> Result<(String, u16), String> {
+    let (host, port) = endpoint
+        .rsplit_once(':')
+        .ok_or("Endpoint is missing a port")?;
+    let port = port
+        .trim()
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid endpoint port: {}", port))?;
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err("Endpoint is missing a host".to_string());
+    }
+    Ok((host.to_string(), port))
+}
+
+pub fn parse_conf(text: &str) -> Result<WireGuardConfig, String> {
+    let mut interface: HashMap<String, String> = HashMap::new();
+    let mut peers: Vec<HashMap<String, String>> = Vec::new();
+    let mut section = String::new();
+
+    for raw_line in text.lines() {
+        let line = raw_line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_lowercase();
+            if section == "peer" {
+                peers.push(HashMap::new());
+            }
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(format!("Invalid line: {}", line))?;
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_string();
+
+        let target = match section.as_str() {
+            "interface" => &mut interface,
+            "peer" => peers.last_mut().unwrap(),
+            _ => return Err(format!("Key {} is outside of a section", key)),
+        };
+        // wg-quick allows Address, DNS and AllowedIPs to be repeated
+        target
+            .entry(key)
+            .and_modify(|existing| {
+                existing.push(',');
+                existing.push_str(&value);
+            })
+            .or_insert(value);
+    }
+
+    if peers.is_empty() {
+        return Err("No [Peer] section found".to_string());
+    }
+    if peers.len() > 1 {
+        return Err("Configs with more than one [Peer] are not supported".to_string());
+    }
+    let peer = &peers[0];
+
+    let private_key = interface
+        .get("privatekey")
+        .cloned()
+        .ok_or("Missing PrivateKey in [Interface]")?;
+    let addresses = split_list(interface.get("address").map(|a| a.as_str()).unwrap_or(""));
+    if addresses.is_empty() {
+        return Err("Missing Address in [Interface]".to_string());
+    }
+    let mtu = match interface.get("mtu") {
+        Some(mtu) => Some(
+            mtu.parse::<u32>()
+                .map_err(|_| format!("Invalid MTU: {}", mtu))?,
+        ),
+        None => None,
+    };
+
+    // wg-quick mixes search domains into DNS, sing-box only takes the servers
+    let dns: Vec<String> = split_list(interface.get("dns").map(|d| d.as_str()).unwrap_or(""))
+        .into_iter()
+        .filter(|d| d.parse::<IpAddr>().is_ok())
+        .collect();
+
+    let peer_public_key = peer
+        .get("publickey")
+        .cloned()
+        .ok_or("Missing PublicKey in [Peer]")?;
+    let (endpoint_host, endpoint_port) =
+        split_endpoint(peer.get("endpoint").ok_or("Missing Endpoint in [Peer]")?)?;
+
+    Ok(WireGuardConfig {
+        private_key,
+        addresses,
+        dns,
+        mtu,
+        peer_public_key,
+        preshared_key: peer.get("presharedkey").cloned(),
+        allowed_ips: split_list(peer.get("allowedips").map(|a| a.as_str()).unwrap_or("")),
+        endpoint_host,
+        endpoint_port,
+    })
+}
+
+impl WireGuardConfig {
+    /// Encodes the config as a `wireguard://` link understood by `parse_outbound`.
+    pub fn to_link(&self, name: &str) -> String {
+        let host = if self.endpoint_host.contains(':') {
+            format!("[{}]", self.endpoint_host)
+        } else {
+            self.endpoint_host.clone()
+        };
+
+        let mut params = vec![
+            format!("public_key={}", urlencoding::encode(&self.peer_public_key)),
+            format!("ip={}", urlencoding::encode(&self.addresses.join(","))),
+        ];
+        if let Some(psk) = &self.preshared_key {
+            params.push(format!("preshared_key={}", urlencoding::encode(psk)));
+        }
+        if !self.allowed_ips.is_empty() {
+            params.push(format!(
+                "allowed_ips={}",
+                urlencoding::encode(&self.allowed_ips.join(","))
+            ));
+        }
+        if let Some(mtu) = self.mtu {
+            params.push(format!("mtu={}", mtu));
+        }
+        if !self.dns.is_empty() {
+            params.push(format!("dns={}", urlencoding::encode(&self.dns.join(","))));
+        }
+
+        format!(
+            "wireguard://{}@{}:{}?{}#{}",
+            urlencoding::encode(&self.private_key),
+            host,
+            self.endpoint_port,
+            params.join("&"),
+            urlencoding::encode(name)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::share_link::ShareLink;
+
+    const CONF: &str = "\
+[Interface]
+PrivateKey = yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=
+Address = 10.8.0.2/32, fd00:8::2/128
+DNS = 10.8.0.1, corp.example
+MTU = 1420
+
+[Peer]
+PublicKey = xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=
+PresharedKey = FpCyhws9cxwWoV4xELtfJvjJN+zQVRPISllRWgeopVE=
+AllowedIPs = 0.0.0.0/0
+AllowedIPs = ::/0
+Endpoint = [2001:db8::1]:51820
+PersistentKeepalive = 25
+";
+
+    #[test]
+    fn parses_wg_quick_conf() {
+        let conf = parse_conf(CONF).unwrap();
+        assert_eq!(conf.addresses, vec!["10.8.0.2/32", "fd00:8::2/128"]);
+        assert_eq!(conf.dns, vec!["10.8.0.1"]);
+        assert_eq!(conf.mtu, Some(1420));
+        assert_eq!(
+            conf.preshared_key.as_deref(),
+            Some("FpCyhws9cxwWoV4xELtfJvjJN+zQVRPISllRWgeopVE=")
+        );
+        assert_eq!(conf.allowed_ips, vec!["0.0.0.0/0", "::/0"]);
+        assert_eq!(conf.endpoint_host, "2001:db8::1");
+        assert_eq!(conf.endpoint_port, 51820);
+
+        let link = ShareLink::parse_checked(&conf.to_link("Office")).unwrap();
+        let outbound = link.to_outbound(&Default::default()).unwrap();
+        assert_eq!(outbound["server"], "2001:db8::1");
+        assert_eq!(
+            outbound["private_key"],
+            "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk="
+        );
+        assert_eq!(outbound["local_address"][1], "fd00:8::2/128");
+        assert_eq!(outbound["mtu"], 1420);
+        assert_eq!(
+            outbound["pre_shared_key"],
+            "FpCyhws9cxwWoV4xELtfJvjJN+zQVRPISllRWgeopVE="
+        );
+        assert_eq!(outbound["peers"][0]["allowed_ips"][1], "::/0");
+    }
+
+    #[test]
+    fn rejects_incomplete_confs() {
+        assert!(parse_conf("[Interface]\nPrivateKey = a\nAddress = 10.0.0.2/32\n").is_err());
+        let two_peers = format!("{}\n[Peer]\nPublicKey = b\nEndpoint = b.example:1\n", CONF);
+        assert!(parse_conf(&two_peers).is_err());
+    }
+}